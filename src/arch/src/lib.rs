@@ -6,12 +6,23 @@
 #![warn(clippy::undocumented_unsafe_blocks)]
 #![warn(clippy::cast_lossless)]
 //! Implements platform specific functionality.
-//! Supported platforms: x86_64 and aarch64.
+//! Supported platforms: x86_64, aarch64 and riscv64.
 use std::{fmt, result};
 
 use versionize::{VersionMap, Versionize, VersionizeError, VersionizeResult};
 use versionize_derive::Versionize;
 
+/// Module for describing a guest's NUMA topology.
+pub mod numa;
+
+/// Module for describing a guest's PCI segments.
+pub mod pci;
+
+/// Module for the PVH/HVM direct-boot ABI, used when `EntryPoint::protocol`
+/// is [`BootProtocol::PvhBoot`].
+#[cfg(target_arch = "x86_64")]
+pub mod pvh;
+
 /// Module for aarch64 related functionality.
 #[cfg(target_arch = "aarch64")]
 pub mod aarch64;
@@ -34,9 +45,61 @@ pub use crate::x86_64::{
     MMIO_MEM_START,
 };
 
+/// Module for riscv64 related functionality.
+#[cfg(target_arch = "riscv64")]
+pub mod riscv64;
+
+#[cfg(target_arch = "riscv64")]
+pub use riscv64::{
+    arch_memory_regions, configure_system, get_kernel_start, initrd_load_addr,
+    layout::CMDLINE_MAX_SIZE, layout::IRQ_BASE, layout::IRQ_MAX, regs, Error,
+    layout::MMIO_MEM_SIZE, layout::MMIO_MEM_START,
+};
+
 /// Type for returning public functions outcome.
 pub type Result<T> = result::Result<T, Error>;
 
+/// Guest memory type used throughout this crate.
+///
+/// With the `track-dirty-pages` feature enabled, regions carry an
+/// [`vm_memory::bitmap::AtomicBitmap`] instead of the default `()` backend,
+/// so every write made through `configure_system` (cmdline / ACPI tables /
+/// PVH structures on x86_64, FDT on riscv64) is automatically recorded. [`reset_dirty_pages`] then
+/// lets the VMM snapshot and clear a region's accumulated bitmap to stream
+/// just the pages that changed since the last snapshot.
+#[cfg(feature = "track-dirty-pages")]
+pub type GuestMemoryMmap = vm_memory::GuestMemoryMmap<vm_memory::bitmap::AtomicBitmap>;
+
+/// Guest memory type used throughout this crate.
+#[cfg(not(feature = "track-dirty-pages"))]
+pub type GuestMemoryMmap = vm_memory::GuestMemoryMmap<()>;
+
+/// Guest memory region type used throughout this crate, matching
+/// [`GuestMemoryMmap`]'s bitmap backend.
+#[cfg(feature = "track-dirty-pages")]
+pub type GuestRegionMmap = vm_memory::GuestRegionMmap<vm_memory::bitmap::AtomicBitmap>;
+
+/// Guest memory region type used throughout this crate.
+#[cfg(not(feature = "track-dirty-pages"))]
+pub type GuestRegionMmap = vm_memory::GuestRegionMmap<()>;
+
+/// Returns the indices, relative to the start of `region`, of every
+/// [`PAGE_SIZE`] page dirtied since the last call, and clears the region's
+/// dirty bitmap so the next call only reports pages dirtied since now.
+#[cfg(feature = "track-dirty-pages")]
+pub fn reset_dirty_pages(region: &GuestRegionMmap) -> Vec<usize> {
+    use vm_memory::bitmap::Bitmap;
+    use vm_memory::GuestMemoryRegion;
+
+    let bitmap = region.bitmap();
+    let num_pages = region.len() as usize / PAGE_SIZE;
+    let dirty_pages = (0..num_pages)
+        .filter(|&page| bitmap.dirty_at(page * PAGE_SIZE))
+        .collect();
+    bitmap.reset();
+    dirty_pages
+}
+
 /// Types of devices that can get attached to this platform.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Copy, Versionize)]
 pub enum DeviceType {
@@ -48,6 +111,9 @@ pub enum DeviceType {
     /// Device Type: RTC.
     #[cfg(target_arch = "aarch64")]
     Rtc,
+    /// Device Type: Serial.
+    #[cfg(target_arch = "riscv64")]
+    Serial,
     /// Device Type: BootTimer.
     BootTimer,
 }
@@ -61,8 +127,53 @@ pub struct InitrdConfig {
 }
 
 /// Default (smallest) memory page size for the supported architectures.
+/// Used as a fallback for address math when the real page size can't be
+/// queried; guest-memory allocation should use [`page_size`] instead.
 pub const PAGE_SIZE: usize = 4096;
 
+/// Returns the host's runtime page size, as reported by
+/// `sysconf(_SC_PAGESIZE)`.
+///
+/// Falls back to [`PAGE_SIZE`] in the (practically unreachable, but
+/// POSIX-permitted) case where the query fails.
+pub fn page_size() -> usize {
+    // SAFETY: `_SC_PAGESIZE` is a valid `sysconf` name; the call has no
+    // other preconditions and cannot fail in a way that is unsafe to
+    // observe.
+    let value = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    usize::try_from(value).unwrap_or(PAGE_SIZE)
+}
+
+/// Selects the page size guest RAM is backed with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum HugePageConfig {
+    /// Back guest memory with the host's native page size ([`page_size`]).
+    #[default]
+    None,
+    /// Back guest memory with 2 MiB hugepages.
+    Size2M,
+    /// Back guest memory with 1 GiB hugepages.
+    Size1G,
+}
+
+impl HugePageConfig {
+    /// Returns the page size, in bytes, that this configuration backs guest
+    /// memory with.
+    pub fn page_size(&self) -> usize {
+        match self {
+            HugePageConfig::None => page_size(),
+            HugePageConfig::Size2M => 2 << 20,
+            HugePageConfig::Size1G => 1 << 30,
+        }
+    }
+
+    /// Rounds `size` up to a multiple of this configuration's page size.
+    pub fn align(&self, size: usize) -> usize {
+        let page_size = self.page_size();
+        (size + page_size - 1) & !(page_size - 1)
+    }
+}
+
 impl fmt::Display for DeviceType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -87,6 +198,50 @@ impl fmt::Display for BootProtocol {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_huge_page_config_align() {
+        assert_eq!(HugePageConfig::Size2M.page_size(), 2 << 20);
+        assert_eq!(HugePageConfig::Size1G.page_size(), 1 << 30);
+
+        // Already aligned sizes are left untouched.
+        assert_eq!(HugePageConfig::Size2M.align(2 << 20), 2 << 20);
+        // Anything else is rounded up to the next page boundary.
+        assert_eq!(HugePageConfig::Size2M.align(1), 2 << 20);
+        assert_eq!(HugePageConfig::Size2M.align((2 << 20) + 1), 2 * (2 << 20));
+        assert_eq!(HugePageConfig::Size1G.align(1), 1 << 30);
+
+        assert_eq!(HugePageConfig::None.align(0), 0);
+    }
+
+    #[cfg(feature = "track-dirty-pages")]
+    #[test]
+    fn test_reset_dirty_pages() {
+        use vm_memory::bitmap::Bitmap;
+        use vm_memory::{Bytes, GuestAddress, GuestMemory, GuestMemoryRegion};
+
+        let guest_mem = GuestMemoryMmap::from_ranges(&[(GuestAddress(0), 3 * PAGE_SIZE)]).unwrap();
+        let region = guest_mem.find_region(GuestAddress(0)).unwrap();
+
+        // Nothing has been written yet, so there is nothing to report.
+        assert!(reset_dirty_pages(region).is_empty());
+
+        guest_mem.write_obj(1u8, GuestAddress(0)).unwrap();
+        guest_mem
+            .write_obj(1u8, GuestAddress(2 * PAGE_SIZE as u64))
+            .unwrap();
+
+        assert_eq!(reset_dirty_pages(region), vec![0, 2]);
+
+        // The bitmap was cleared by the call above.
+        assert!(!region.bitmap().dirty_at(0));
+        assert!(reset_dirty_pages(region).is_empty());
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 /// Specifies the entry point address where the guest must start
 /// executing code, as well as which boot protocol is to be used
@@ -0,0 +1,54 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Describes a guest's NUMA topology so it can be honored by the
+//! platform-specific `configure_system` (FDT on aarch64/riscv64,
+//! ACPI SRAT/SLIT on x86_64).
+//!
+//! The crate only carries the topology description: building one from a
+//! user-supplied configuration and pinning vCPU threads to host nodes are
+//! the VMM's responsibility.
+
+use std::collections::BTreeMap;
+
+use vm_memory::GuestAddress;
+
+use crate::DeviceType;
+
+/// Identifier of a NUMA node, unique within a single guest's topology.
+pub type NumaNodeId = u32;
+
+/// Describes a single NUMA node: the guest memory ranges backing it, the
+/// vCPUs pinned to it, the devices attached to it, and its distance to
+/// every other node in the topology.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NumaNode {
+    /// Guest memory ranges assigned to this node, as (base, size) pairs.
+    pub memory_regions: Vec<(GuestAddress, usize)>,
+    /// vCPU ids pinned to this node.
+    pub vcpu_ids: Vec<u8>,
+    /// Devices attached to this node.
+    pub devices: Vec<DeviceType>,
+    /// Relative distance from this node to every other node, keyed by node
+    /// id. A node's distance to itself is conventionally 10.
+    pub distances: BTreeMap<NumaNodeId, u32>,
+}
+
+impl NumaNode {
+    /// Creates an empty NUMA node.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total amount of guest memory, in bytes, backed by this node.
+    pub fn memory_size(&self) -> usize {
+        self.memory_regions.iter().map(|(_, size)| size).sum()
+    }
+}
+
+/// Full description of a guest's NUMA topology, keyed by node id.
+///
+/// A `BTreeMap` is used (rather than a `Vec`) so node ids can be sparse and
+/// so the topology is always enumerated in a stable, increasing order when
+/// it is serialized into the platform-specific tables.
+pub type NumaTopology = BTreeMap<NumaNodeId, NumaNode>;
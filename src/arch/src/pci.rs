@@ -0,0 +1,22 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Describes the PCI config space and MMIO windows handed to
+//! `configure_system` so the generated FDT (riscv64/aarch64) or ACPI tables
+//! (x86_64) can describe more than a single PCI segment.
+
+/// Describes one PCI segment (a.k.a. domain): where its ECAM config space
+/// lives in guest physical memory, and the MMIO window devices on that
+/// segment hand out BARs from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PciSpaceInfo {
+    /// Identifier of this PCI segment, exposed to the guest as
+    /// `linux,pci-domain` / the ACPI `_SEG` value.
+    pub segment_id: u16,
+    /// Base guest physical address of this segment's ECAM config space.
+    pub config_space_addr: u64,
+    /// Base guest physical address of this segment's MMIO window.
+    pub mmio_start: u64,
+    /// Size, in bytes, of this segment's MMIO window.
+    pub mmio_size: u64,
+}
@@ -0,0 +1,247 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Structures for the PVH/HVM direct-boot ABI (`BootProtocol::PvhBoot`).
+//!
+//! These mirror Xen's `public/arch-x86/hvm/start_info.h`: a guest entered
+//! under this protocol finds a pointer to a `hvm_start_info` in its `%ebx`
+//! register instead of a Linux zero page, and reaches everything else
+//! (the E820-equivalent memory map, the cmdline, the initrd) by following
+//! the pointers inside it.
+//!
+//! `x86_64::configure_system` lays these out in guest memory, with the
+//! `hvm_start_info` at `x86_64::layout::PVH_INFO_START`, when
+//! `EntryPoint::protocol` is [`crate::BootProtocol::PvhBoot`]. It does not
+//! set any vCPU register.
+
+use vm_memory::{Address, ByteValued, Bytes, GuestAddress, GuestMemory, GuestMemoryError};
+
+/// Errors thrown while laying out the PVH/HVM direct-boot structures in
+/// guest memory.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The `hvm_start_info` struct doesn't fit before the end of guest RAM.
+    #[error("hvm_start_info address past the end of guest memory")]
+    StartInfoPastRamEnd,
+    /// Failed to write the `hvm_start_info` struct to guest memory.
+    #[error("Failed to write hvm_start_info to guest memory: {0}")]
+    StartInfoSetup(GuestMemoryError),
+    /// The `hvm_memmap_table_entry` array doesn't fit before the end of
+    /// guest RAM.
+    #[error("hvm_memmap_table_entry table address past the end of guest memory")]
+    MemmapTablePastRamEnd,
+    /// Failed to write the `hvm_memmap_table_entry` array to guest memory.
+    #[error("Failed to write the memory map table to guest memory: {0}")]
+    MemmapTableSetup(GuestMemoryError),
+    /// The `hvm_modlist_entry` array doesn't fit before the end of guest
+    /// RAM.
+    #[error("hvm_modlist_entry table address past the end of guest memory")]
+    ModlistPastRamEnd,
+    /// Failed to write the `hvm_modlist_entry` array to guest memory.
+    #[error("Failed to write the module list to guest memory: {0}")]
+    ModlistSetup(GuestMemoryError),
+}
+
+/// Value of [`HvmStartInfo::magic`] identifying a valid `hvm_start_info`.
+pub const XEN_HVM_START_MAGIC_VALUE: u32 = 0x336e_c578;
+
+/// `hvm_start_info` version produced by this crate.
+pub const HVM_START_INFO_VERSION: u32 = 1;
+
+/// A normal RAM entry in the [`HvmMemmapTableEntry`] table.
+pub const E820_RAM: u32 = 1;
+/// A reserved entry in the [`HvmMemmapTableEntry`] table.
+pub const E820_RESERVED: u32 = 2;
+
+/// The `hvm_start_info` struct handed to a PVH guest, pointed to by `%ebx`
+/// on entry.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
+pub struct HvmStartInfo {
+    /// Must be [`XEN_HVM_START_MAGIC_VALUE`].
+    pub magic: u32,
+    /// Version of this layout; [`HVM_START_INFO_VERSION`] here.
+    pub version: u32,
+    /// Flags, currently unused by this crate.
+    pub flags: u32,
+    /// Number of entries in the module list at `modlist_paddr`.
+    pub nr_modules: u32,
+    /// Guest physical address of an array of [`HvmModlistEntry`], or 0.
+    pub modlist_paddr: u64,
+    /// Guest physical address of the NUL-terminated kernel command line.
+    pub cmdline_paddr: u64,
+    /// Guest physical address of the ACPI RSDP, or 0 if none is provided.
+    pub rsdp_paddr: u64,
+    /// Guest physical address of an array of [`HvmMemmapTableEntry`].
+    pub memmap_paddr: u64,
+    /// Number of entries in the array at `memmap_paddr`.
+    pub memmap_entries: u32,
+    /// Reserved, must be zero.
+    pub reserved: u32,
+}
+
+// SAFETY: `HvmStartInfo` is a plain-old-data struct with no padding that
+// would expose uninitialized bytes, safe to read and write byte-for-byte.
+unsafe impl ByteValued for HvmStartInfo {}
+
+/// One entry of the E820-equivalent memory map pointed to by
+/// [`HvmStartInfo::memmap_paddr`].
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
+pub struct HvmMemmapTableEntry {
+    /// Base guest physical address of this range.
+    pub addr: u64,
+    /// Size, in bytes, of this range.
+    pub size: u64,
+    /// Type of this range, e.g. [`E820_RAM`] or [`E820_RESERVED`].
+    pub mem_type: u32,
+    /// Reserved, must be zero.
+    pub reserved: u32,
+}
+
+// SAFETY: `HvmMemmapTableEntry` is a plain-old-data struct with no padding
+// that would expose uninitialized bytes, safe to read and write
+// byte-for-byte.
+unsafe impl ByteValued for HvmMemmapTableEntry {}
+
+/// One entry of the module list pointed to by [`HvmStartInfo::modlist_paddr`],
+/// used here to describe the initrd.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
+pub struct HvmModlistEntry {
+    /// Guest physical address where the module is loaded.
+    pub paddr: u64,
+    /// Size, in bytes, of the module.
+    pub size: u64,
+    /// Guest physical address of the module's NUL-terminated command line,
+    /// or 0 if it has none.
+    pub cmdline_paddr: u64,
+    /// Reserved, must be zero.
+    pub reserved: u64,
+}
+
+// SAFETY: `HvmModlistEntry` is a plain-old-data struct with no padding that
+// would expose uninitialized bytes, safe to read and write byte-for-byte.
+unsafe impl ByteValued for HvmModlistEntry {}
+
+/// Returns whether `base + len - 1` (inclusive) is still within
+/// `end_of_ram`, guarding against both `u64` overflow and the range simply
+/// running past the end of guest memory.
+fn fits_before(base: GuestAddress, len: u64, end_of_ram: GuestAddress) -> bool {
+    match len.checked_sub(1).and_then(|last| base.checked_add(last)) {
+        Some(last_addr) => last_addr <= end_of_ram,
+        None => len == 0,
+    }
+}
+
+/// Writes the `hvm_start_info` struct, its memory map and (if present) its
+/// module list into `guest_mem`, and returns the guest physical address of
+/// the `hvm_start_info` struct so it can be handed to the guest in `%ebx`.
+///
+/// # Arguments
+///
+/// * `guest_mem` - The guest memory to write into.
+/// * `start_info_addr` - Where to write the `hvm_start_info` struct itself.
+/// * `memmap_addr` - Where to write the E820-equivalent memory map.
+/// * `memmap` - The memory map entries to write at `memmap_addr`.
+/// * `cmdline_addr` - Guest physical address of the NUL-terminated cmdline.
+/// * `modlist_addr` - Where to write the module list, if `initrd` is given.
+/// * `initrd` - Where the initrd was loaded and how big it is, if one is
+///   attached.
+/// * `rsdp_addr` - Guest physical address of the ACPI RSDP, if the guest
+///   has any ACPI tables to describe (e.g. SRAT/SLIT/MCFG).
+#[allow(clippy::too_many_arguments)]
+pub fn setup_start_info<M: GuestMemory>(
+    guest_mem: &M,
+    start_info_addr: GuestAddress,
+    memmap_addr: GuestAddress,
+    memmap: &[HvmMemmapTableEntry],
+    cmdline_addr: GuestAddress,
+    modlist_addr: GuestAddress,
+    initrd: &Option<(GuestAddress, usize)>,
+    rsdp_addr: Option<GuestAddress>,
+) -> Result<GuestAddress, Error> {
+    let end_of_ram = guest_mem.last_addr();
+
+    let start_info_size = std::mem::size_of::<HvmStartInfo>() as u64;
+    if !fits_before(start_info_addr, start_info_size, end_of_ram) {
+        return Err(Error::StartInfoPastRamEnd);
+    }
+
+    let entry_size = std::mem::size_of::<HvmMemmapTableEntry>() as u64;
+    let memmap_size = entry_size * memmap.len() as u64;
+    if !fits_before(memmap_addr, memmap_size, end_of_ram) {
+        return Err(Error::MemmapTablePastRamEnd);
+    }
+    for (i, entry) in memmap.iter().enumerate() {
+        let entry_addr = memmap_addr
+            .checked_add(i as u64 * entry_size)
+            .ok_or(Error::MemmapTablePastRamEnd)?;
+        guest_mem
+            .write_obj(*entry, entry_addr)
+            .map_err(Error::MemmapTableSetup)?;
+    }
+
+    let nr_modules = u32::from(initrd.is_some());
+    if let Some((initrd_addr, initrd_size)) = initrd {
+        let modlist_size = std::mem::size_of::<HvmModlistEntry>() as u64;
+        if !fits_before(modlist_addr, modlist_size, end_of_ram) {
+            return Err(Error::ModlistPastRamEnd);
+        }
+
+        let modlist_entry = HvmModlistEntry {
+            paddr: initrd_addr.raw_value(),
+            size: *initrd_size as u64,
+            cmdline_paddr: 0,
+            reserved: 0,
+        };
+        guest_mem
+            .write_obj(modlist_entry, modlist_addr)
+            .map_err(Error::ModlistSetup)?;
+    }
+
+    let start_info = HvmStartInfo {
+        magic: XEN_HVM_START_MAGIC_VALUE,
+        version: HVM_START_INFO_VERSION,
+        flags: 0,
+        nr_modules,
+        modlist_paddr: if nr_modules > 0 {
+            modlist_addr.raw_value()
+        } else {
+            0
+        },
+        cmdline_paddr: cmdline_addr.raw_value(),
+        rsdp_paddr: rsdp_addr.map_or(0, |addr| addr.raw_value()),
+        memmap_paddr: memmap_addr.raw_value(),
+        memmap_entries: memmap.len() as u32,
+        reserved: 0,
+    };
+    guest_mem
+        .write_obj(start_info, start_info_addr)
+        .map_err(Error::StartInfoSetup)?;
+
+    Ok(start_info_addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fits_before() {
+        let end_of_ram = GuestAddress(0xff);
+
+        // A zero-length range always fits, even past the end of RAM.
+        assert!(fits_before(GuestAddress(0x100), 0, end_of_ram));
+
+        // Ranges entirely within bounds, or ending exactly at the last byte.
+        assert!(fits_before(GuestAddress(0), 0x100, end_of_ram));
+        assert!(fits_before(GuestAddress(0xf0), 0x10, end_of_ram));
+
+        // A range that runs one byte past the end of RAM doesn't fit.
+        assert!(!fits_before(GuestAddress(0xf0), 0x11, end_of_ram));
+
+        // `base + len` overflowing `u64` doesn't fit either.
+        assert!(!fits_before(GuestAddress(u64::MAX), 2, end_of_ram));
+    }
+}
@@ -0,0 +1,273 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Flattened device tree generation for riscv64 guests.
+
+use std::ffi::CStr;
+
+use vm_fdt::{FdtWriter, FdtWriterResult};
+use vm_memory::{Address, GuestMemory};
+
+use super::layout::{CLINT_SIZE, CLINT_START, DRAM_MEM_START, PLIC_SIZE, PLIC_START};
+use crate::numa::{NumaNodeId, NumaTopology};
+use crate::pci::PciSpaceInfo;
+use crate::{GuestMemoryMmap, InitrdConfig};
+
+/// Creates the flattened device tree for a riscv64 guest and returns it as a
+/// byte blob; `configure_system` writes it into guest memory.
+///
+/// # Arguments
+///
+/// * `guest_mem` - The guest memory the FDT will describe and be written to.
+/// * `cmdline` - The kernel command line.
+/// * `num_harts` - The number of harts (vCPUs) present in the guest.
+/// * `initrd` - The initrd location and size, if one is attached.
+/// * `numa_topology` - The guest's NUMA topology, if it has more than one
+///   node.
+/// * `pci_space_info` - The guest's PCI segments, one `pci` node is emitted
+///   per entry.
+pub fn create_fdt(
+    guest_mem: &GuestMemoryMmap,
+    cmdline: &CStr,
+    num_harts: u32,
+    initrd: &Option<InitrdConfig>,
+    numa_topology: Option<&NumaTopology>,
+    pci_space_info: &[PciSpaceInfo],
+) -> FdtWriterResult<Vec<u8>> {
+    let mut fdt = FdtWriter::new()?;
+
+    let root = fdt.begin_node("")?;
+    fdt.property_u32("#address-cells", 2)?;
+    fdt.property_u32("#size-cells", 2)?;
+    fdt.property_string("compatible", "riscv-virtio")?;
+    fdt.property_string("model", "riscv-firecracker")?;
+
+    create_cpu_nodes(&mut fdt, num_harts, numa_topology)?;
+    create_memory_nodes(&mut fdt, guest_mem, numa_topology)?;
+    create_chosen_node(&mut fdt, cmdline, initrd)?;
+    create_plic_node(&mut fdt, num_harts)?;
+    create_clint_node(&mut fdt, num_harts)?;
+    if let Some(topology) = numa_topology {
+        create_distance_map_node(&mut fdt, topology)?;
+    }
+    for segment in pci_space_info {
+        create_pci_node(&mut fdt, segment)?;
+    }
+
+    fdt.end_node(root)?;
+
+    fdt.finish()
+}
+
+/// Returns the id of the NUMA node `hart_id` is pinned to, if any.
+fn node_id_for_hart(topology: Option<&NumaTopology>, hart_id: u32) -> Option<NumaNodeId> {
+    let hart_id = u8::try_from(hart_id).ok()?;
+    topology.and_then(|topology| {
+        topology
+            .iter()
+            .find(|(_, node)| node.vcpu_ids.contains(&hart_id))
+            .map(|(&node_id, _)| node_id)
+    })
+}
+
+fn create_cpu_nodes(
+    fdt: &mut FdtWriter,
+    num_harts: u32,
+    numa_topology: Option<&NumaTopology>,
+) -> FdtWriterResult<()> {
+    let cpus = fdt.begin_node("cpus")?;
+    fdt.property_u32("#address-cells", 1)?;
+    fdt.property_u32("#size-cells", 0)?;
+    fdt.property_u32("timebase-frequency", 10_000_000)?;
+
+    for hart_id in 0..num_harts {
+        let cpu = fdt.begin_node(&format!("cpu@{:x}", hart_id))?;
+        fdt.property_string("device_type", "cpu")?;
+        fdt.property_string("compatible", "riscv")?;
+        fdt.property_string("mmu-type", "riscv,sv48")?;
+        fdt.property_string("status", "okay")?;
+        fdt.property_u32("reg", hart_id)?;
+        if let Some(node_id) = node_id_for_hart(numa_topology, hart_id) {
+            fdt.property_u32("numa-node-id", node_id)?;
+        }
+
+        let intc = fdt.begin_node("interrupt-controller")?;
+        fdt.property_u32("#interrupt-cells", 1)?;
+        fdt.property_null("interrupt-controller")?;
+        fdt.property_string("compatible", "riscv,cpu-intc")?;
+        fdt.property_u32("phandle", plic_context_phandle(hart_id))?;
+        fdt.end_node(intc)?;
+
+        fdt.end_node(cpu)?;
+    }
+
+    fdt.end_node(cpus)?;
+    Ok(())
+}
+
+/// Emits one `memory` node per memory region of each NUMA node when a
+/// topology is given, each tagged with its node's `numa-node-id`, or a
+/// single node spanning all of RAM otherwise.
+fn create_memory_nodes(
+    fdt: &mut FdtWriter,
+    guest_mem: &GuestMemoryMmap,
+    numa_topology: Option<&NumaTopology>,
+) -> FdtWriterResult<()> {
+    match numa_topology {
+        Some(topology) => {
+            for (&node_id, node) in topology {
+                for &(base, size) in &node.memory_regions {
+                    let memory =
+                        fdt.begin_node(&format!("memory@{:x}", base.raw_value()))?;
+                    fdt.property_string("device_type", "memory")?;
+                    fdt.property_array_u64("reg", &[base.raw_value(), size as u64])?;
+                    fdt.property_u32("numa-node-id", node_id)?;
+                    fdt.end_node(memory)?;
+                }
+            }
+            Ok(())
+        }
+        None => {
+            let mem_size = guest_mem.last_addr().raw_value() - DRAM_MEM_START + 1;
+            let memory = fdt.begin_node(&format!("memory@{:x}", DRAM_MEM_START))?;
+            fdt.property_string("device_type", "memory")?;
+            fdt.property_array_u64("reg", &[DRAM_MEM_START, mem_size])?;
+            fdt.end_node(memory)?;
+            Ok(())
+        }
+    }
+}
+
+/// Emits the `distance-map` node describing the relative distance between
+/// every pair of NUMA nodes, per the `numa-distance-map-v1` binding.
+fn create_distance_map_node(fdt: &mut FdtWriter, topology: &NumaTopology) -> FdtWriterResult<()> {
+    let mut matrix = Vec::new();
+    for (&from, node) in topology {
+        for (&to, &distance) in &node.distances {
+            matrix.push(from);
+            matrix.push(to);
+            matrix.push(distance);
+        }
+    }
+
+    let distance_map = fdt.begin_node("distance-map")?;
+    fdt.property_string("compatible", "numa-distance-map-v1")?;
+    fdt.property_array_u32("distance-matrix", &matrix)?;
+    fdt.end_node(distance_map)?;
+    Ok(())
+}
+
+fn create_chosen_node(
+    fdt: &mut FdtWriter,
+    cmdline: &CStr,
+    initrd: &Option<InitrdConfig>,
+) -> FdtWriterResult<()> {
+    let chosen = fdt.begin_node("chosen")?;
+    fdt.property_string("bootargs", cmdline.to_str().unwrap_or(""))?;
+
+    if let Some(initrd_config) = initrd {
+        let start = initrd_config.address.raw_value();
+        let end = start + initrd_config.size as u64;
+        fdt.property_u64("linux,initrd-start", start)?;
+        fdt.property_u64("linux,initrd-end", end)?;
+    }
+
+    fdt.end_node(chosen)?;
+    Ok(())
+}
+
+/// Supervisor-mode external interrupt cause, used in the PLIC's
+/// `interrupts-extended` context map.
+const PLIC_S_MODE_CONTEXT: u32 = 9;
+/// Supervisor software interrupt cause, used in CLINT's MSIP binding.
+const CLINT_S_MODE_SOFTWARE_IRQ: u32 = 1;
+/// Supervisor timer interrupt cause, used in CLINT's MTIMECMP binding.
+const CLINT_S_MODE_TIMER_IRQ: u32 = 5;
+
+fn create_plic_node(fdt: &mut FdtWriter, num_harts: u32) -> FdtWriterResult<()> {
+    let plic = fdt.begin_node(&format!("plic@{:x}", PLIC_START))?;
+    fdt.property_string("compatible", "riscv,plic0")?;
+    fdt.property_u32("#interrupt-cells", 1)?;
+    fdt.property_u32("#address-cells", 0)?;
+    fdt.property_null("interrupt-controller")?;
+    fdt.property_array_u64("reg", &[PLIC_START, PLIC_SIZE])?;
+    fdt.property_u32("riscv,ndev", super::layout::IRQ_MAX)?;
+    fdt.property_u32("phandle", PLIC_PHANDLE)?;
+
+    // Binds each hart's S-mode external interrupt context to this PLIC, so
+    // the guest's PLIC driver can route a pending IRQ to the hart handling
+    // it.
+    let interrupts_extended: Vec<u32> = (0..num_harts)
+        .flat_map(|hart_id| [plic_context_phandle(hart_id), PLIC_S_MODE_CONTEXT])
+        .collect();
+    fdt.property_array_u32("interrupts-extended", &interrupts_extended)?;
+
+    fdt.end_node(plic)?;
+    Ok(())
+}
+
+fn create_clint_node(fdt: &mut FdtWriter, num_harts: u32) -> FdtWriterResult<()> {
+    let clint = fdt.begin_node(&format!("clint@{:x}", CLINT_START))?;
+    fdt.property_string("compatible", "riscv,clint0")?;
+    fdt.property_array_u64("reg", &[CLINT_START, CLINT_SIZE])?;
+
+    // Binds each hart's MSIP (software interrupt) and MTIMECMP (timer
+    // interrupt) lines to this CLINT, so the guest's timer/IPI driver can
+    // bind to it.
+    let interrupts_extended: Vec<u32> = (0..num_harts)
+        .flat_map(|hart_id| {
+            [
+                plic_context_phandle(hart_id),
+                CLINT_S_MODE_SOFTWARE_IRQ,
+                plic_context_phandle(hart_id),
+                CLINT_S_MODE_TIMER_IRQ,
+            ]
+        })
+        .collect();
+    fdt.property_array_u32("interrupts-extended", &interrupts_extended)?;
+
+    fdt.end_node(clint)?;
+    Ok(())
+}
+
+/// Emits a `pci` node for one PCI segment, per the `pci-host-ecam-generic`
+/// binding: an ECAM config space region and one `ranges` entry mapping its
+/// MMIO window 1:1 into guest physical memory.
+fn create_pci_node(fdt: &mut FdtWriter, segment: &PciSpaceInfo) -> FdtWriterResult<()> {
+    let pci = fdt.begin_node(&format!("pci@{:x}", segment.config_space_addr))?;
+    fdt.property_string("compatible", "pci-host-ecam-generic")?;
+    fdt.property_string("device_type", "pci")?;
+    fdt.property_u32("#address-cells", 3)?;
+    fdt.property_u32("#size-cells", 2)?;
+    fdt.property_u32("linux,pci-domain", u32::from(segment.segment_id))?;
+    fdt.property_array_u32("bus-range", &[0, 255])?;
+    fdt.property_array_u64(
+        "reg",
+        &[
+            segment.config_space_addr,
+            super::layout::PCI_CONFIG_SPACE_SIZE,
+        ],
+    )?;
+    // A `ranges` entry for a 32-bit, non-prefetchable MMIO window, mapped
+    // 1:1 from guest physical address to PCI bus address.
+    fdt.property_array_u32(
+        "ranges",
+        &[
+            0x0200_0000,
+            (segment.mmio_start >> 32) as u32,
+            segment.mmio_start as u32,
+            (segment.mmio_start >> 32) as u32,
+            segment.mmio_start as u32,
+            (segment.mmio_size >> 32) as u32,
+            segment.mmio_size as u32,
+        ],
+    )?;
+    fdt.end_node(pci)?;
+    Ok(())
+}
+
+const PLIC_PHANDLE: u32 = 1;
+
+fn plic_context_phandle(hart_id: u32) -> u32 {
+    PLIC_PHANDLE + 1 + hart_id
+}
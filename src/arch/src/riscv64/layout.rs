@@ -0,0 +1,50 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Memory layout for the riscv64 architecture.
+//!
+//! The guest physical memory map mirrors the one used for aarch64: a single
+//! DRAM region starting above the MMIO window. The kernel is loaded at
+//! [`super::get_kernel_start`], the initrd at [`super::initrd_load_addr`],
+//! and [`super::configure_system`] writes the flattened device tree into the
+//! last [`FDT_MAX_SIZE`] bytes of it.
+
+/// Start of the 64-bit MMIO window reserved for virtio/PCI devices.
+pub const MMIO_MEM_START: u64 = 0x0;
+
+/// Size of the MMIO window reserved for virtio/PCI devices.
+pub const MMIO_MEM_SIZE: u64 = 0x8000_0000;
+
+/// The first usable DRAM address, and also where the kernel is loaded.
+///
+/// RISC-V Linux expects to be entered with DRAM mapped starting here; it
+/// matches the reset vector wired up by the `virt` machine model.
+pub const DRAM_MEM_START: u64 = 0x8000_0000;
+
+/// Maximum size of the cmdline passed through the FDT `/chosen` node.
+pub const CMDLINE_MAX_SIZE: usize = 4096;
+
+/// First usable interrupt number for virtio-mmio devices behind the PLIC.
+pub const IRQ_BASE: u32 = 1;
+
+/// Last usable interrupt number for virtio-mmio devices behind the PLIC.
+pub const IRQ_MAX: u32 = 192;
+
+/// Base address of the platform-level interrupt controller (PLIC).
+pub const PLIC_START: u64 = 0xc00_0000;
+
+/// Size of the PLIC memory-mapped register window.
+pub const PLIC_SIZE: u64 = 0x400_0000;
+
+/// Base address of the core-local interruptor (CLINT).
+pub const CLINT_START: u64 = 0x200_0000;
+
+/// Size of the CLINT memory-mapped register window.
+pub const CLINT_SIZE: u64 = 0x1_0000;
+
+/// Maximum size in bytes for the flattened device tree.
+pub const FDT_MAX_SIZE: usize = 0x20_0000;
+
+/// Size, in bytes, of a single PCI segment's ECAM config space: 256 buses *
+/// 32 devices * 8 functions * 4 KiB per function.
+pub const PCI_CONFIG_SPACE_SIZE: u64 = 256 * 32 * 8 * 4096;
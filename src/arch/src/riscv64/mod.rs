@@ -0,0 +1,155 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implements platform specific functionality for riscv64.
+//!
+//! riscv64 guests are booted the same way aarch64 guests are: there is no
+//! zero-page/`boot_params` ABI to fill in, so the whole machine description
+//! (memory, cmdline, initrd, interrupt controllers) is handed to the guest
+//! kernel through a flattened device tree.
+
+use std::ffi::CStr;
+
+use vm_memory::{Address, Bytes, GuestAddress, GuestMemory};
+
+use crate::numa::NumaTopology;
+use crate::pci::PciSpaceInfo;
+use crate::{GuestMemoryMmap, HugePageConfig, InitrdConfig};
+
+mod fdt;
+/// Layout for the riscv64 guest physical address space.
+pub mod layout;
+/// Logic for configuring riscv64 registers.
+pub mod regs;
+
+/// Errors thrown while configuring riscv64 system.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Failed to create FDT.
+    #[error("Failed to create a FDT: {0}")]
+    CreateFdt(#[from] vm_fdt::Error),
+    /// Failed to write FDT to guest memory.
+    #[error("Failed to write FDT to guest memory: {0}")]
+    WriteFdt(vm_memory::GuestMemoryError),
+    /// Failed to compute initrd address.
+    #[error("Initrd address past end of guest memory")]
+    InitrdAddress,
+}
+
+/// Type for returning public functions outcome.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The start of the memory area reserved for the FDT: the last
+/// [`layout::FDT_MAX_SIZE`] bytes of guest RAM.
+fn fdt_addr(guest_mem: &GuestMemoryMmap) -> u64 {
+    guest_mem.last_addr().raw_value() - layout::FDT_MAX_SIZE as u64 + 1
+}
+
+/// Returns a Vec of the valid memory addresses for riscv64.
+///
+/// RAM starts at [`layout::DRAM_MEM_START`] and is followed, without any
+/// holes, for `size` bytes. This mirrors the aarch64 layout: the MMIO window
+/// lives below RAM, so there is nothing to carve out of the single region.
+///
+/// When `numa_topology` is given, the single RAM region is split along the
+/// node boundaries already recorded in each `NumaNode`, in node id order, so
+/// the regions returned here line up with the ranges `configure_system`
+/// later tags with a `numa-node-id` property.
+///
+/// `huge_page_config` selects the page size backing the region(s); `size`
+/// (and each NUMA node's region sizes) is rounded up to a multiple of it so
+/// the guest memory can actually be mapped with that backing page size.
+///
+/// The regions themselves are returned as plain `(GuestAddress, usize)`
+/// ranges, independent of the dirty-bitmap backend: it's the VMM, building
+/// the actual [`crate::GuestMemoryMmap`] from these ranges (e.g. via
+/// `vm_memory::GuestMemoryMmap::from_ranges`), that picks up whichever
+/// backend that type alias resolves to. With the `track-dirty-pages`
+/// feature enabled, that's `vm_memory::bitmap::AtomicBitmap`, so every
+/// region built from the ranges below already carries the bitmap
+/// [`crate::reset_dirty_pages`] reads from — no separate plumbing needed
+/// here.
+pub fn arch_memory_regions(
+    size: usize,
+    numa_topology: Option<&NumaTopology>,
+    huge_page_config: HugePageConfig,
+) -> Vec<(GuestAddress, usize)> {
+    match numa_topology {
+        Some(topology) => topology
+            .values()
+            .flat_map(|node| node.memory_regions.iter().copied())
+            .map(|(addr, size)| (addr, huge_page_config.align(size)))
+            .collect(),
+        None => vec![(
+            GuestAddress(layout::DRAM_MEM_START),
+            huge_page_config.align(size),
+        )],
+    }
+}
+
+/// Configures the flattened device tree and writes it, along with anything
+/// else the guest kernel expects to find in memory, into `guest_mem`.
+///
+/// # Arguments
+///
+/// * `guest_mem` - The guest memory to configure.
+/// * `cmdline_cstring` - The kernel command line.
+/// * `num_harts` - The number of vCPUs (harts) the guest is configured with.
+/// * `initrd` - Information about the initrd, if one is attached.
+/// * `numa_topology` - The guest's NUMA topology, if it has more than one
+///   node.
+/// * `pci_space_info` - The guest's PCI segments, one `pci` node is emitted
+///   per entry.
+pub fn configure_system(
+    guest_mem: &GuestMemoryMmap,
+    cmdline_cstring: &CStr,
+    num_harts: u32,
+    initrd: &Option<InitrdConfig>,
+    numa_topology: Option<&NumaTopology>,
+    pci_space_info: &[PciSpaceInfo],
+) -> Result<()> {
+    let fdt = fdt::create_fdt(
+        guest_mem,
+        cmdline_cstring,
+        num_harts,
+        initrd,
+        numa_topology,
+        pci_space_info,
+    )
+    .map_err(Error::CreateFdt)?;
+
+    let fdt_address = GuestAddress(fdt_addr(guest_mem));
+    guest_mem
+        .write_slice(fdt.as_slice(), fdt_address)
+        .map_err(Error::WriteFdt)?;
+
+    Ok(())
+}
+
+/// Returns the memory address where the kernel is loaded.
+pub fn get_kernel_start() -> u64 {
+    layout::DRAM_MEM_START
+}
+
+/// Returns the memory address where the initrd could be loaded.
+///
+/// The address is rounded down so the initrd starts on a `huge_page_config`
+/// page boundary, matching the alignment `arch_memory_regions` gave the
+/// region it lives in.
+pub fn initrd_load_addr(
+    guest_mem: &GuestMemoryMmap,
+    initrd_size: usize,
+    huge_page_config: HugePageConfig,
+) -> Result<u64> {
+    let aligned_size = huge_page_config.align(initrd_size) as u64;
+    let page_mask = huge_page_config.page_size() as u64 - 1;
+    let addr = GuestAddress(fdt_addr(guest_mem))
+        .checked_sub(aligned_size)
+        .map(|addr| GuestAddress(addr.raw_value() & !page_mask))
+        .ok_or(Error::InitrdAddress)?;
+    if addr >= GuestAddress(layout::DRAM_MEM_START) {
+        Ok(addr.raw_value())
+    } else {
+        Err(Error::InitrdAddress)
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Boot hart register setup for riscv64.
+//!
+//! The RISC-V Linux boot convention requires the boot hart to be entered
+//! with `a0` holding its own hart id and `a1` holding the physical address
+//! of the flattened device tree describing the machine.
+
+use std::result;
+
+/// Errors thrown while setting up the boot hart registers.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Failed to set core register.
+    #[error("Failed to set core register: {0}")]
+    SetCoreRegister(#[from] kvm_ioctls::Error),
+}
+
+/// Type for returning public functions outcome.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Configures the general purpose registers of the boot hart so it enters
+/// the kernel per the RISC-V Linux boot convention: `a0` = hart id, `a1` =
+/// FDT address.
+///
+/// # Arguments
+///
+/// * `vcpu` - Structure for the vCPU that holds the vCPU's fd.
+/// * `hart_id` - The id of the boot hart as seen by the guest.
+/// * `fdt_addr` - The guest physical address where the FDT was written.
+pub fn setup_boot_regs(vcpu: &kvm_ioctls::VcpuFd, hart_id: u64, fdt_addr: u64) -> Result<()> {
+    vcpu.set_one_reg(riscv64_reg_id(RISCV_CORE_REG_A0), u128::from(hart_id))
+        .map_err(Error::SetCoreRegister)?;
+    vcpu.set_one_reg(riscv64_reg_id(RISCV_CORE_REG_A1), u128::from(fdt_addr))
+        .map_err(Error::SetCoreRegister)?;
+    Ok(())
+}
+
+// KVM one_reg ids for the riscv64 core registers we touch. These mirror the
+// `KVM_REG_RISCV_CORE` layout from `arch/riscv/include/uapi/asm/kvm.h`.
+const RISCV_CORE_REG_A0: u64 = 10;
+const RISCV_CORE_REG_A1: u64 = 11;
+
+fn riscv64_reg_id(reg: u64) -> u64 {
+    // KVM_REG_RISCV | KVM_REG_SIZE_U64 | KVM_REG_RISCV_CORE | register index.
+    0x8000_0000_0000_0000 | (0x0030 << 48) | (0x02 << 24) | reg
+}
@@ -0,0 +1,200 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Minimal ACPI tables describing a guest's NUMA topology (SRAT/SLIT) and
+//! PCI segments (MCFG), built for the x86_64 `configure_system` path.
+//!
+//! Unlike [`crate::pvh`]'s structures, most ACPI tables are variable-length
+//! (a fixed header followed by a table-specific number of subtype
+//! entries), so these are assembled as raw byte buffers rather than
+//! `#[repr(C)]` structs.
+
+use vm_memory::{Address, Bytes, GuestAddress, GuestMemory};
+
+use crate::numa::NumaTopology;
+use crate::pci::PciSpaceInfo;
+
+/// Errors thrown while building or writing the ACPI tables.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The combined ACPI tables don't fit before the end of guest RAM.
+    #[error("ACPI tables address past the end of guest memory")]
+    PastRamEnd,
+    /// Failed to write the ACPI tables to guest memory.
+    #[error("Failed to write ACPI tables to guest memory: {0}")]
+    Setup(vm_memory::GuestMemoryError),
+}
+
+const OEM_ID: &[u8; 6] = b"FRCRKR";
+const CREATOR_ID: &[u8; 4] = b"FRCK";
+
+/// Returns the byte that makes the sum of every byte in `bytes` (including
+/// this one) equal 0 mod 256, per the ACPI table checksum rule.
+fn checksum(bytes: &[u8]) -> u8 {
+    0u8.wrapping_sub(bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)))
+}
+
+/// Appends a standard 36-byte ACPI SDT header followed by `body` to `buf`,
+/// then backpatches the header's `length` and `checksum` fields. Returns
+/// the table's offset into `buf`.
+fn append_table(buf: &mut Vec<u8>, signature: &[u8; 4], body: &[u8]) -> usize {
+    let offset = buf.len();
+    buf.extend_from_slice(signature);
+    buf.extend_from_slice(&[0u8; 4]); // length, backpatched below
+    buf.push(1); // revision
+    buf.push(0); // checksum, backpatched below
+    buf.extend_from_slice(OEM_ID);
+    buf.extend_from_slice(&[0u8; 8]); // oem_table_id
+    buf.extend_from_slice(&1u32.to_le_bytes()); // oem_revision
+    buf.extend_from_slice(CREATOR_ID);
+    buf.extend_from_slice(&1u32.to_le_bytes()); // creator_revision
+    buf.extend_from_slice(body);
+
+    let length = (buf.len() - offset) as u32;
+    buf[offset + 4..offset + 8].copy_from_slice(&length.to_le_bytes());
+    buf[offset + 9] = checksum(&buf[offset..offset + length as usize]);
+    offset
+}
+
+/// Builds the SRAT: one Processor Local APIC Affinity structure per vCPU
+/// and one Memory Affinity structure per memory region, each tagged with
+/// its node's proximity domain (the NUMA node id).
+fn build_srat(topology: &NumaTopology) -> Vec<u8> {
+    let mut body = vec![0u8; 12]; // reserved (4) + reserved (8)
+
+    for (&node_id, node) in topology {
+        for &vcpu_id in &node.vcpu_ids {
+            body.push(0); // type: Processor Local APIC Affinity
+            body.push(16); // length
+            body.push(node_id as u8); // proximity domain [7:0]
+            body.push(vcpu_id); // APIC ID
+            body.extend_from_slice(&1u32.to_le_bytes()); // flags: enabled
+            body.push(0); // local SAPIC EID
+            body.extend_from_slice(&(node_id >> 8).to_le_bytes()[..3]); // proximity domain [31:8]
+            body.extend_from_slice(&0u32.to_le_bytes()); // clock domain
+        }
+
+        for &(base, size) in &node.memory_regions {
+            body.push(1); // type: Memory Affinity
+            body.push(40); // length
+            body.extend_from_slice(&node_id.to_le_bytes()); // proximity domain
+            body.extend_from_slice(&0u16.to_le_bytes()); // reserved
+            body.extend_from_slice(&(base.raw_value() as u32).to_le_bytes());
+            body.extend_from_slice(&((base.raw_value() >> 32) as u32).to_le_bytes());
+            body.extend_from_slice(&(size as u32).to_le_bytes());
+            body.extend_from_slice(&((size as u64 >> 32) as u32).to_le_bytes());
+            body.extend_from_slice(&0u32.to_le_bytes()); // reserved
+            body.extend_from_slice(&1u32.to_le_bytes()); // flags: enabled
+            body.extend_from_slice(&0u64.to_le_bytes()); // reserved
+        }
+    }
+
+    body
+}
+
+/// Builds the SLIT distance matrix, in node-id order, defaulting to 10 (the
+/// ACPI-defined self-distance) on the diagonal and 20 for any pair the
+/// topology didn't record a distance for.
+fn build_slit(topology: &NumaTopology) -> Vec<u8> {
+    let node_ids: Vec<u32> = topology.keys().copied().collect();
+    let mut body = (node_ids.len() as u64).to_le_bytes().to_vec();
+
+    for &from in &node_ids {
+        for &to in &node_ids {
+            let distance = if from == to {
+                10
+            } else {
+                topology[&from].distances.get(&to).copied().unwrap_or(20)
+            };
+            body.push(distance as u8);
+        }
+    }
+
+    body
+}
+
+/// Builds the MCFG: one Configuration Space Allocation Structure per PCI
+/// segment, pointing at its ECAM config space.
+fn build_mcfg(pci_space_info: &[PciSpaceInfo]) -> Vec<u8> {
+    let mut body = vec![0u8; 8]; // reserved
+
+    for segment in pci_space_info {
+        body.extend_from_slice(&segment.config_space_addr.to_le_bytes());
+        body.extend_from_slice(&segment.segment_id.to_le_bytes());
+        body.push(0); // start bus number
+        body.push(255); // end bus number
+        body.extend_from_slice(&0u32.to_le_bytes()); // reserved
+    }
+
+    body
+}
+
+/// Writes the RSDP, XSDT and (when applicable) SRAT, SLIT and MCFG tables
+/// describing `numa_topology` and `pci_space_info` into `guest_mem`
+/// starting at `acpi_start`, and returns the guest physical address of the
+/// RSDP.
+///
+/// Returns `Ok(None)` without writing anything if there is nothing to
+/// describe (no NUMA topology and no PCI segments).
+pub fn build_acpi_tables<M: GuestMemory>(
+    guest_mem: &M,
+    acpi_start: GuestAddress,
+    numa_topology: Option<&NumaTopology>,
+    pci_space_info: &[PciSpaceInfo],
+) -> Result<Option<GuestAddress>, Error> {
+    if numa_topology.is_none() && pci_space_info.is_empty() {
+        return Ok(None);
+    }
+
+    // The XSDT is appended last, once every other table's address is
+    // known, so tables are assembled into their own buffer first.
+    let mut tables = Vec::new();
+    let mut table_addrs = Vec::new();
+    let mut push_table = |tables: &mut Vec<u8>, signature: &[u8; 4], body: Vec<u8>| {
+        let offset = append_table(tables, signature, &body);
+        table_addrs.push(acpi_start.raw_value() + offset as u64);
+    };
+
+    if let Some(topology) = numa_topology {
+        push_table(&mut tables, b"SRAT", build_srat(topology));
+        push_table(&mut tables, b"SLIT", build_slit(topology));
+    }
+    if !pci_space_info.is_empty() {
+        push_table(&mut tables, b"MCFG", build_mcfg(pci_space_info));
+    }
+
+    let xsdt_entries: Vec<u8> = table_addrs.iter().flat_map(|a| a.to_le_bytes()).collect();
+    let xsdt_offset = append_table(&mut tables, b"XSDT", &xsdt_entries);
+    let xsdt_addr = acpi_start.raw_value() + xsdt_offset as u64;
+
+    let mut rsdp = Vec::with_capacity(36);
+    rsdp.extend_from_slice(b"RSD PTR ");
+    rsdp.push(0); // checksum (ACPI 1.0 portion), backpatched below
+    rsdp.extend_from_slice(OEM_ID);
+    rsdp.push(2); // revision: ACPI 2.0+
+    rsdp.extend_from_slice(&0u32.to_le_bytes()); // rsdt_address: unused, we only emit an XSDT
+    rsdp.extend_from_slice(&36u32.to_le_bytes()); // length
+    rsdp.extend_from_slice(&xsdt_addr.to_le_bytes());
+    rsdp.push(0); // extended checksum, backpatched below
+    rsdp.extend_from_slice(&[0u8; 3]); // reserved
+    rsdp[8] = checksum(&rsdp[0..20]);
+    rsdp[32] = checksum(&rsdp);
+
+    let rsdp_offset = tables.len();
+    tables.extend_from_slice(&rsdp);
+    let rsdp_addr = GuestAddress(acpi_start.raw_value() + rsdp_offset as u64);
+
+    let end_of_ram = guest_mem.last_addr();
+    let last_addr = acpi_start
+        .checked_add(tables.len() as u64 - 1)
+        .ok_or(Error::PastRamEnd)?;
+    if last_addr > end_of_ram {
+        return Err(Error::PastRamEnd);
+    }
+
+    guest_mem
+        .write_slice(&tables, acpi_start)
+        .map_err(Error::Setup)?;
+
+    Ok(Some(rsdp_addr))
+}
@@ -0,0 +1,49 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Memory layout for the x86_64 architecture.
+
+/// Address where guest RAM starts. Kept at 0 so the sub-1 MiB structures
+/// (cmdline, PVH start info) `configure_system` writes are themselves
+/// within the mapped region, not just below [`HIMEM_START`].
+pub const DRAM_MEM_START: u64 = 0x0;
+
+/// Address where the 64-bit kernel is loaded, right above the BIOS/legacy
+/// real-mode area.
+pub const HIMEM_START: u64 = 0x0010_0000;
+
+/// Start of the MMIO window reserved for virtio/PCI devices, below 4 GiB.
+pub const MMIO_MEM_START: u64 = 0xd000_0000;
+
+/// Size of the MMIO window reserved for virtio/PCI devices.
+pub const MMIO_MEM_SIZE: u64 = 0x2000_0000;
+
+/// Maximum size of the kernel command line.
+pub const CMDLINE_MAX_SIZE: usize = 4096;
+
+/// First usable legacy IRQ line for virtio-mmio devices.
+pub const IRQ_BASE: u32 = 5;
+
+/// Last usable legacy IRQ line for virtio-mmio devices.
+pub const IRQ_MAX: u32 = 23;
+
+/// Guest physical address the NUL-terminated kernel command line is copied
+/// to.
+pub const CMDLINE_START: u64 = 0x0002_0000;
+
+/// Guest physical address the `hvm_start_info` struct is written to for the
+/// PVH boot protocol.
+pub const PVH_INFO_START: u64 = 0x0006_0000;
+
+/// Guest physical address the `hvm_memmap_table_entry` array is written to
+/// for the PVH boot protocol.
+pub const PVH_MEMMAP_START: u64 = 0x0007_0000;
+
+/// Guest physical address the `hvm_modlist_entry` array (describing the
+/// initrd) is written to for the PVH boot protocol.
+pub const PVH_MODLIST_START: u64 = 0x0008_0000;
+
+/// Guest physical address the ACPI tables are written to when the guest has
+/// a NUMA topology (SRAT/SLIT) or at least one PCI segment (MCFG), followed
+/// by the XSDT and the RSDP. Nothing is written here otherwise.
+pub const ACPI_START: u64 = 0x0009_0000;
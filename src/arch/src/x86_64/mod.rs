@@ -0,0 +1,171 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implements platform specific functionality for x86_64.
+
+use std::ffi::CStr;
+
+use vm_memory::{Address, Bytes, GuestAddress, GuestMemory};
+
+use crate::numa::NumaTopology;
+use crate::pci::PciSpaceInfo;
+use crate::pvh::HvmMemmapTableEntry;
+use crate::{BootProtocol, EntryPoint, GuestMemoryMmap, HugePageConfig, InitrdConfig};
+
+/// ACPI tables (SRAT/SLIT/MCFG) describing NUMA topology and PCI segments.
+mod acpi;
+/// Layout for the x86_64 guest physical address space.
+pub mod layout;
+
+pub use layout::{MMIO_MEM_SIZE, MMIO_MEM_START};
+
+/// Errors thrown while configuring the x86_64 system.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Failed to write the kernel command line to guest memory.
+    #[error("Failed to write the kernel command line to guest memory: {0}")]
+    Cmdline(vm_memory::GuestMemoryError),
+    /// Failed to lay out the PVH/HVM direct-boot structures.
+    #[error("Failed to configure PVH boot: {0}")]
+    Pvh(#[from] crate::pvh::Error),
+    /// Failed to lay out the ACPI tables.
+    #[error("Failed to configure ACPI tables: {0}")]
+    Acpi(#[from] acpi::Error),
+}
+
+/// Type for returning public functions outcome.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Returns a Vec of the valid memory addresses for x86_64.
+///
+/// RAM starts at [`layout::DRAM_MEM_START`] and is followed, without any
+/// holes, for `size` bytes.
+///
+/// When `numa_topology` is given, the single RAM region is split along the
+/// node boundaries already recorded in each `NumaNode`, in node id order, so
+/// the regions returned here line up with the SRAT memory affinity entries
+/// `configure_system` later builds from the same topology.
+///
+/// `huge_page_config` selects the page size backing the region(s); `size`
+/// (and each NUMA node's region sizes) is rounded up to a multiple of it so
+/// the guest memory can actually be mapped with that backing page size.
+pub fn arch_memory_regions(
+    size: usize,
+    numa_topology: Option<&NumaTopology>,
+    huge_page_config: HugePageConfig,
+) -> Vec<(GuestAddress, usize)> {
+    match numa_topology {
+        Some(topology) => topology
+            .values()
+            .flat_map(|node| node.memory_regions.iter().copied())
+            .map(|(addr, size)| (addr, huge_page_config.align(size)))
+            .collect(),
+        None => vec![(
+            GuestAddress(layout::DRAM_MEM_START),
+            huge_page_config.align(size),
+        )],
+    }
+}
+
+/// Returns the memory address where the kernel is loaded.
+pub fn get_kernel_start() -> u64 {
+    layout::HIMEM_START
+}
+
+/// Returns the memory address where the initrd could be loaded.
+///
+/// The address is rounded down so the initrd starts on a `huge_page_config`
+/// page boundary, matching the alignment `arch_memory_regions` gave the
+/// region it lives in.
+pub fn initrd_load_addr(
+    guest_mem: &GuestMemoryMmap,
+    initrd_size: usize,
+    huge_page_config: HugePageConfig,
+) -> Result<u64> {
+    let aligned_size = huge_page_config.align(initrd_size) as u64;
+    let page_mask = huge_page_config.page_size() as u64 - 1;
+    let last_addr = guest_mem.last_addr().raw_value();
+    let addr = (last_addr - aligned_size + 1) & !page_mask;
+    Ok(addr)
+}
+
+fn write_cmdline(guest_mem: &GuestMemoryMmap, cmdline_cstring: &CStr) -> Result<()> {
+    guest_mem
+        .write_slice(
+            cmdline_cstring.to_bytes_with_nul(),
+            GuestAddress(layout::CMDLINE_START),
+        )
+        .map_err(Error::Cmdline)
+}
+
+/// Builds the E820-equivalent memory map describing every region returned
+/// by `arch_memory_regions` as RAM.
+fn build_memmap(memory_regions: &[(GuestAddress, usize)]) -> Vec<HvmMemmapTableEntry> {
+    memory_regions
+        .iter()
+        .map(|&(addr, size)| HvmMemmapTableEntry {
+            addr: addr.raw_value(),
+            size: size as u64,
+            mem_type: crate::pvh::E820_RAM,
+            reserved: 0,
+        })
+        .collect()
+}
+
+/// Writes the guest memory state for `entry_point`'s boot protocol into
+/// `guest_mem`: the kernel command line, the ACPI tables describing
+/// `numa_topology`/`pci_space_info` (when either is non-trivial) and (for
+/// [`BootProtocol::PvhBoot`]) the PVH/HVM direct-boot structures. Only
+/// `entry_point.protocol` is read; no vCPU register is set here.
+///
+/// # Arguments
+///
+/// * `guest_mem` - The guest memory to configure.
+/// * `cmdline_cstring` - The kernel command line.
+/// * `memory_regions` - The guest RAM regions, as returned by
+///   `arch_memory_regions`.
+/// * `initrd` - Information about the initrd, if one is attached.
+/// * `entry_point` - Where the guest starts executing, and under which
+///   boot protocol.
+/// * `numa_topology` - The guest's NUMA topology, if it has more than one
+///   node. Emitted as SRAT (memory/processor affinity) and SLIT
+///   (locality distance) tables.
+/// * `pci_space_info` - The guest's PCI segments, emitted as an MCFG table
+///   describing each segment's ECAM config space.
+pub fn configure_system(
+    guest_mem: &GuestMemoryMmap,
+    cmdline_cstring: &CStr,
+    memory_regions: &[(GuestAddress, usize)],
+    initrd: &Option<InitrdConfig>,
+    entry_point: &EntryPoint,
+    numa_topology: Option<&NumaTopology>,
+    pci_space_info: &[PciSpaceInfo],
+) -> Result<()> {
+    write_cmdline(guest_mem, cmdline_cstring)?;
+
+    let rsdp_addr = acpi::build_acpi_tables(
+        guest_mem,
+        GuestAddress(layout::ACPI_START),
+        numa_topology,
+        pci_space_info,
+    )?;
+
+    if let BootProtocol::PvhBoot = entry_point.protocol {
+        let memmap = build_memmap(memory_regions);
+        let initrd = initrd
+            .as_ref()
+            .map(|initrd_config| (initrd_config.address, initrd_config.size));
+        crate::pvh::setup_start_info(
+            guest_mem,
+            GuestAddress(layout::PVH_INFO_START),
+            GuestAddress(layout::PVH_MEMMAP_START),
+            &memmap,
+            GuestAddress(layout::CMDLINE_START),
+            GuestAddress(layout::PVH_MODLIST_START),
+            &initrd,
+            rsdp_addr,
+        )?;
+    }
+
+    Ok(())
+}